@@ -0,0 +1,65 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use futures::Stream;
+use tokio::io::AsyncRead;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::lidar::{D300, D300Error, Frame};
+
+/// Drives a single `D300` in the background and fans its frames out to any
+/// number of [`subscribe`](D300Broadcast::subscribe)rs. A subscriber that
+/// falls behind the configured buffer is told so via `RecvError::Lagged`
+/// rather than stalling the background reader for everyone else. The last
+/// item every subscriber sees is the `D300Error` (including a clean EOF's
+/// `UnexpectedEof`) that stopped the background reader.
+pub struct D300Broadcast {
+    tx: broadcast::Sender<Arc<Result<Frame, D300Error>>>,
+    task: JoinHandle<()>,
+}
+
+impl D300Broadcast {
+    pub fn subscribe(&self) -> Pin<Box<dyn Stream<Item = Result<Arc<Result<Frame, D300Error>>, broadcast::error::RecvError>>>> {
+        Box::pin(futures::stream::unfold(self.tx.subscribe(), |mut rx| async move {
+            match rx.recv().await {
+                Ok(item) => Some((Ok(item), rx)),
+                Err(broadcast::error::RecvError::Closed) => None,
+                Err(e) => Some((Err(e), rx)),
+            }
+        }))
+    }
+
+    /// Stops the background reader task, closing every subscriber's stream.
+    pub fn abort(self) {
+        self.task.abort();
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> D300<R> {
+    /// Takes ownership of the device and drives `read_frame` in a background
+    /// task, publishing each frame to a `buffer`-sized broadcast channel. Call
+    /// `subscribe()` on the returned handle for each independent consumer.
+    ///
+    /// # Panics
+    /// Panics if `buffer` is zero (forwarded from `broadcast::channel`).
+    pub fn spawn_broadcast(mut self, buffer: usize) -> D300Broadcast {
+        let (tx, _) = broadcast::channel(buffer);
+        let sender = tx.clone();
+
+        let task = tokio::spawn(async move {
+            loop {
+                match self.read_frame().await {
+                    Ok(frame) => {
+                        let _ = sender.send(Arc::new(Ok(frame)));
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Arc::new(Err(err)));
+                        break;
+                    }
+                }
+            }
+        });
+
+        D300Broadcast { tx, task }
+    }
+}
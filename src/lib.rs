@@ -1,6 +1,10 @@
 mod lidar;
+mod record;
+mod broadcast;
 
-pub use crate::lidar::D300;
+pub use crate::lidar::{D300, D300Error};
+pub use crate::record::{Recorder, Replayer};
+pub use crate::broadcast::D300Broadcast;
 #[cfg(feature = "usb")]
 pub use usb_support::*;
 
@@ -12,7 +16,6 @@ mod usb_support {
 
     use std::time::Duration;
     use super::*;
-    use tokio::io::{BufReader};
     use tokio_serial::{SerialPortBuilderExt, SerialStream};
 
     impl D300<SerialStream> {
@@ -21,9 +24,7 @@ mod usb_support {
                 .timeout(timeout)
                 .open_native_async()?;
 
-            Ok(Self {
-                rdr: BufReader::new(port),
-            })
+            Ok(Self::new(port))
         }
     }
 }
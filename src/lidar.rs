@@ -1,13 +1,66 @@
-use std::io::Error;
 use std::pin::Pin;
 use futures::{Stream, StreamExt};
 use futures::future::ready;
 use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 
+/// Errors surfaced by the fallible frame/scan-line streams.
 #[derive(Debug)]
-pub struct ScanLine {
-    pub distance: u16,
-    pub intensity: u8,
+pub enum D300Error {
+    /// The underlying transport returned an I/O error other than EOF.
+    Io(std::io::Error),
+    /// The trailing CRC8 byte did not match what was computed over the frame.
+    Crc { expected: u8, computed: u8 },
+    /// The stream ended, either between frames or part-way through one.
+    UnexpectedEof,
+}
+
+impl From<std::io::Error> for D300Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => D300Error::UnexpectedEof,
+            _ => D300Error::Io(err),
+        }
+    }
+}
+
+/// CRC8 lookup table for the LD-series polynomial (0x4D), indexed by `crc ^ byte`.
+const CRC_TABLE: [u8; 256] = [
+    0x00, 0x4D, 0x9A, 0xD7, 0x79, 0x34, 0xE3, 0xAE,
+    0xF2, 0xBF, 0x68, 0x25, 0x8B, 0xC6, 0x11, 0x5C,
+    0xA9, 0xE4, 0x33, 0x7E, 0xD0, 0x9D, 0x4A, 0x07,
+    0x5B, 0x16, 0xC1, 0x8C, 0x22, 0x6F, 0xB8, 0xF5,
+    0x1F, 0x52, 0x85, 0xC8, 0x66, 0x2B, 0xFC, 0xB1,
+    0xED, 0xA0, 0x77, 0x3A, 0x94, 0xD9, 0x0E, 0x43,
+    0xB6, 0xFB, 0x2C, 0x61, 0xCF, 0x82, 0x55, 0x18,
+    0x44, 0x09, 0xDE, 0x93, 0x3D, 0x70, 0xA7, 0xEA,
+    0x3E, 0x73, 0xA4, 0xE9, 0x47, 0x0A, 0xDD, 0x90,
+    0xCC, 0x81, 0x56, 0x1B, 0xB5, 0xF8, 0x2F, 0x62,
+    0x97, 0xDA, 0x0D, 0x40, 0xEE, 0xA3, 0x74, 0x39,
+    0x65, 0x28, 0xFF, 0xB2, 0x1C, 0x51, 0x86, 0xCB,
+    0x21, 0x6C, 0xBB, 0xF6, 0x58, 0x15, 0xC2, 0x8F,
+    0xD3, 0x9E, 0x49, 0x04, 0xAA, 0xE7, 0x30, 0x7D,
+    0x88, 0xC5, 0x12, 0x5F, 0xF1, 0xBC, 0x6B, 0x26,
+    0x7A, 0x37, 0xE0, 0xAD, 0x03, 0x4E, 0x99, 0xD4,
+    0x7C, 0x31, 0xE6, 0xAB, 0x05, 0x48, 0x9F, 0xD2,
+    0x8E, 0xC3, 0x14, 0x59, 0xF7, 0xBA, 0x6D, 0x20,
+    0xD5, 0x98, 0x4F, 0x02, 0xAC, 0xE1, 0x36, 0x7B,
+    0x27, 0x6A, 0xBD, 0xF0, 0x5E, 0x13, 0xC4, 0x89,
+    0x63, 0x2E, 0xF9, 0xB4, 0x1A, 0x57, 0x80, 0xCD,
+    0x91, 0xDC, 0x0B, 0x46, 0xE8, 0xA5, 0x72, 0x3F,
+    0xCA, 0x87, 0x50, 0x1D, 0xB3, 0xFE, 0x29, 0x64,
+    0x38, 0x75, 0xA2, 0xEF, 0x41, 0x0C, 0xDB, 0x96,
+    0x42, 0x0F, 0xD8, 0x95, 0x3B, 0x76, 0xA1, 0xEC,
+    0xB0, 0xFD, 0x2A, 0x67, 0xC9, 0x84, 0x53, 0x1E,
+    0xEB, 0xA6, 0x71, 0x3C, 0x92, 0xDF, 0x08, 0x45,
+    0x19, 0x54, 0x83, 0xCE, 0x60, 0x2D, 0xFA, 0xB7,
+    0x5D, 0x10, 0xC7, 0x8A, 0x24, 0x69, 0xBE, 0xF3,
+    0xAF, 0xE2, 0x35, 0x78, 0xD6, 0x9B, 0x4C, 0x01,
+    0xF4, 0xB9, 0x6E, 0x23, 0x8D, 0xC0, 0x17, 0x5A,
+    0x06, 0x4B, 0x9C, 0xD1, 0x7F, 0x32, 0xE5, 0xA8,
+];
+
+pub(crate) fn crc8_update(crc: u8, bytes: &[u8]) -> u8 {
+    bytes.iter().fold(crc, |crc, &byte| CRC_TABLE[(crc ^ byte) as usize])
 }
 
 #[derive(Debug)]
@@ -24,7 +77,9 @@ pub struct Frame {
 }
 
 pub struct D300<R: AsyncRead> {
-    pub(crate) rdr: BufReader<R>
+    pub(crate) rdr: BufReader<R>,
+    body_buf: Vec<u8>,
+    data_pool: Vec<Vec<AngledScanLine>>,
 }
 
 #[derive(Debug)]
@@ -34,15 +89,51 @@ pub struct AngledScanLine {
     pub angle: f64
 }
 
+impl AngledScanLine {
+    /// Converts this return to Cartesian millimeters, or `None` if the
+    /// distance is zero/invalid or the intensity is below `min_intensity`.
+    pub fn xy(&self, min_intensity: usize) -> Option<(f64, f64)> {
+        if self.distance == 0 || self.intensity < min_intensity {
+            return None;
+        }
+
+        let angle_rad = self.angle.to_radians();
+        let distance = self.distance as f64;
+        Some((distance * angle_rad.cos(), distance * angle_rad.sin()))
+    }
+}
+
 #[allow(dead_code)]
 impl<R: AsyncRead + Unpin> D300<R> {
-    fn new(reader: R) -> Self {
+    pub fn new(reader: R) -> Self {
         Self {
-            rdr: BufReader::new(reader)
+            rdr: BufReader::new(reader),
+            body_buf: Vec::new(),
+            data_pool: Vec::new(),
         }
     }
 
-    async fn read_frame(&mut self) -> Result<Frame, Error> {
+    /// Returns a consumed frame's point buffer to the pool so the next
+    /// `read_frame` can reuse its allocation instead of growing a new one.
+    pub fn recycle(&mut self, mut frame: Frame) {
+        frame.data.clear();
+        self.data_pool.push(frame.data);
+    }
+
+    pub(crate) async fn read_frame(&mut self) -> Result<Frame, D300Error> {
+        self.read_frame_impl(false).await.map(|(frame, _)| frame)
+    }
+
+    /// Like `read_frame`, but also returns the exact wire bytes (header
+    /// through the trailing CRC) that produced the frame, so a caller (e.g.
+    /// `Recorder`) can persist them for byte-identical replay through this
+    /// same reader.
+    pub(crate) async fn read_frame_raw(&mut self) -> Result<(Frame, Vec<u8>), D300Error> {
+        let (frame, raw) = self.read_frame_impl(true).await?;
+        Ok((frame, raw.unwrap()))
+    }
+
+    async fn read_frame_impl(&mut self, capture_raw: bool) -> Result<(Frame, Option<Vec<u8>>), D300Error> {
         const EXPECTED_HEADER: u8 = 84;
 
         loop {
@@ -55,36 +146,63 @@ impl<R: AsyncRead + Unpin> D300<R> {
             let message_type = msg_info >> 5;
             let len = msg_info & 0x1F;
 
-            let speed = self.rdr.read_u16_le().await?;
-            let start_angle = self.rdr.read_u16_le().await? as f64 / 100.0;
+            // speed(2) + start_angle(2) + len*3 + end_angle(2) + ts(2) + crc(1)
+            let body_len = 9 + len as usize * 3;
+            if self.body_buf.len() < body_len {
+                self.body_buf.resize(body_len, 0);
+            } else {
+                self.body_buf.truncate(body_len);
+            }
+            self.rdr.read_exact(&mut self.body_buf).await?;
+            let body = &self.body_buf;
 
-            let mut line_buffer = Vec::with_capacity(len as usize);
-            for _ in 0..len {
-                let distance = self.rdr.read_u16_le().await?;
-                let intensity = self.rdr.read_u8().await?;
-                line_buffer.push(ScanLine { distance, intensity });
+            let crc = body[body_len - 1];
+            let computed = crc8_update(crc8_update(0, &[header, msg_info]), &body[..body_len - 1]);
+            if computed != crc {
+                return Err(D300Error::Crc { expected: crc, computed });
             }
 
-            let end_angle = self.rdr.read_u16_le().await? as f64 / 100.0;
+            let raw = capture_raw.then(|| {
+                let mut raw = Vec::with_capacity(2 + body_len);
+                raw.push(header);
+                raw.push(msg_info);
+                raw.extend_from_slice(body);
+                raw
+            });
+
+            let speed = u16::from_le_bytes([body[0], body[1]]);
+            let start_angle = u16::from_le_bytes([body[2], body[3]]) as f64 / 100.0;
+
+            let end_angle_offset = 4 + len as usize * 3;
+            let end_angle = u16::from_le_bytes([body[end_angle_offset], body[end_angle_offset + 1]]) as f64 / 100.0;
 
             // TODO: prop. not right!
-            let angle_increment = (end_angle-start_angle)/(len-1) as f64;
+            let angle_increment = if len <= 1 {
+                0.0
+            } else {
+                (end_angle - start_angle) / (len - 1) as f64
+            };
 
-            let mut data = Vec::with_capacity(len as usize);
+            let mut data = self.data_pool.pop().unwrap_or_default();
+            data.clear();
+            data.reserve(len as usize);
 
-            for (i, scanline) in line_buffer.into_iter().enumerate() {
+            for i in 0..len as usize {
+                let offset = 4 + i * 3;
+                let distance = u16::from_le_bytes([body[offset], body[offset + 1]]);
+                let intensity = body[offset + 2];
                 let interpolated_angle = start_angle + angle_increment * i as f64;
                 data.push(AngledScanLine {
-                    distance: scanline.distance as usize,
+                    distance: distance as usize,
                     angle: interpolated_angle,
-                    intensity: scanline.intensity as usize,
+                    intensity: intensity as usize,
                 });
             }
 
-            let ts = self.rdr.read_u16_le().await?;
-            let crc = self.rdr.read_u8().await?;
+            let ts_offset = end_angle_offset + 2;
+            let ts = u16::from_le_bytes([body[ts_offset], body[ts_offset + 1]]);
 
-            return Ok(Frame {
+            return Ok((Frame {
                 header,
                 message_type,
                 len,
@@ -94,45 +212,145 @@ impl<R: AsyncRead + Unpin> D300<R> {
                 end_angle,
                 ts,
                 crc,
-            });
+            }, raw));
         }
     }
 
-    fn as_frame_stream(&mut self) -> Pin<Box<dyn Stream<Item = Frame> + '_>> {
-        Box::pin(futures::stream::unfold(self, |d300| async {
+    /// Reads the next frame, transparently resyncing past CRC mismatches.
+    /// Returns `None` on a clean EOF or a fatal transport error.
+    async fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            match self.read_frame().await {
+                Ok(frame) => return Some(frame),
+                Err(D300Error::Crc { .. }) => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Fallible frame stream: I/O failures and CRC mismatches are surfaced as a
+    /// `D300Error` rather than silently ending the stream, so a consumer can tell a
+    /// recoverable desync (resync on the next header, stream keeps going) from a
+    /// fatal transport failure (stream ends, the error is the last item). A clean
+    /// EOF ends the stream with no final item, same as the infallible wrappers.
+    /// An EOF part-way through a frame is not told apart from a clean one: it
+    /// also ends the stream with no final item.
+    pub fn try_frame_stream(&mut self) -> Pin<Box<dyn Stream<Item = Result<Frame, D300Error>> + '_>> {
+        Box::pin(futures::stream::unfold(Some(self), |state| async {
+            let d300 = state?;
             match d300.read_frame().await {
-                Ok(frame) => Some((frame, d300)),
-                Err(_) => None
+                Ok(frame) => Some((Ok(frame), Some(d300))),
+                Err(D300Error::UnexpectedEof) => None,
+                Err(e @ D300Error::Io(_)) => Some((Err(e), None)),
+                Err(e) => Some((Err(e), Some(d300))),
             }
         }))
     }
 
-    fn as_scan_line_stream(&mut self) -> Pin<Box<dyn Stream<Item = AngledScanLine> + '_>> {
-        Box::pin(self.as_frame_stream().flat_map(|frame: Frame| {
-            futures::stream::iter(frame.data).boxed()
+    pub fn try_scan_line_stream(&mut self) -> Pin<Box<dyn Stream<Item = Result<AngledScanLine, D300Error>> + '_>> {
+        Box::pin(self.try_frame_stream().flat_map(|frame| match frame {
+            Ok(frame) => futures::stream::iter(frame.data.into_iter().map(Ok)).boxed(),
+            Err(e) => futures::stream::iter(vec![Err(e)]).boxed(),
         }))
     }
 
-    fn frame_in(&mut self, rotations: usize) -> Pin<Box<dyn Stream<Item = Vec<AngledScanLine>> + '_>> {
-        let mut line_buffer: Vec<AngledScanLine> = Vec::new();
-        let mut covered_angle = 0.0;
+    pub fn as_frame_stream(&mut self) -> Pin<Box<dyn Stream<Item = Frame> + '_>> {
+        Box::pin(self.try_frame_stream().filter_map(|frame| ready(frame.ok())))
+    }
 
-        Box::pin(self.as_frame_stream().filter_map(move |mut frame: Frame| {
-            covered_angle += if frame.start_angle <= frame.end_angle {
-                frame.end_angle - frame.start_angle
-            } else {
-                (360.0 - frame.start_angle) + frame.end_angle
-            };
+    pub fn as_scan_line_stream(&mut self) -> Pin<Box<dyn Stream<Item = AngledScanLine> + '_>> {
+        Box::pin(self.try_scan_line_stream().filter_map(|line| ready(line.ok())))
+    }
 
-            line_buffer.append(&mut frame.data);
+    /// Drains frames directly (rather than via `as_frame_stream`) so each
+    /// frame's point buffer can be recycled into the pool as soon as its
+    /// points are copied into `line_buffer`. Each completed batch is handed out
+    /// as its own `Vec`, so `line_buffer` starts empty and regrows after every
+    /// yield.
+    pub fn frame_in(&mut self, rotations: usize) -> Pin<Box<dyn Stream<Item = Vec<AngledScanLine>> + '_>> {
+        let state = (self, Vec::<AngledScanLine>::new(), 0.0f64);
 
-            if covered_angle >= rotations as f64 * 360.0 {
-                ready(Some(std::mem::take(&mut line_buffer)))
-            } else {
-                ready(None)
+        Box::pin(futures::stream::unfold(state, move |(d300, mut line_buffer, mut covered_angle)| async move {
+            loop {
+                let mut frame = d300.next_frame().await?;
+
+                covered_angle += if frame.start_angle <= frame.end_angle {
+                    frame.end_angle - frame.start_angle
+                } else {
+                    (360.0 - frame.start_angle) + frame.end_angle
+                };
+
+                line_buffer.append(&mut frame.data);
+                d300.recycle(frame);
+
+                if covered_angle >= rotations as f64 * 360.0 {
+                    let completed = std::mem::take(&mut line_buffer);
+                    return Some((completed, (d300, line_buffer, covered_angle)));
+                }
             }
         }))
     }
+
+    /// Yields one point cloud per full 360° rotation, detected by `start_angle`
+    /// wrapping back below the previous frame's `end_angle`. Only the narrow
+    /// angular window around the 0°/360° wrap is deduplicated (keeping the
+    /// strongest-intensity return per bin there); every other point keeps its
+    /// full device resolution. Drains frames directly (like `frame_in`) so
+    /// each frame's point buffer is recycled into the pool once copied.
+    pub fn sweep_stream(&mut self) -> Pin<Box<dyn Stream<Item = Vec<AngledScanLine>> + '_>> {
+        let state = (self, Vec::<AngledScanLine>::new(), None::<f64>);
+
+        Box::pin(futures::stream::unfold(state, move |(d300, mut sweep, mut last_end_angle)| async move {
+            loop {
+                let mut frame = d300.next_frame().await?;
+
+                let wrapped = matches!(last_end_angle, Some(prev_end) if frame.start_angle < prev_end);
+                last_end_angle = Some(frame.end_angle);
+
+                if wrapped && !sweep.is_empty() {
+                    let completed = dedupe_sweep(std::mem::take(&mut sweep));
+                    sweep.append(&mut frame.data);
+                    d300.recycle(frame);
+                    return Some((completed, (d300, sweep, last_end_angle)));
+                } else {
+                    sweep.append(&mut frame.data);
+                    d300.recycle(frame);
+                }
+            }
+        }))
+    }
+}
+
+/// Degrees either side of the 0°/360° wrap considered to be the overlap region.
+const WRAP_OVERLAP_DEG: f64 = 2.0;
+/// Bin width used to dedupe the overlap region, matching the device's ~0.1° resolution.
+const WRAP_OVERLAP_BIN_DEG: f64 = 0.1;
+
+/// Leaves every point outside the wrap overlap untouched, in original order
+/// (full resolution), and keeps only the strongest-intensity return per
+/// fine-grained bin within the overlap, appended in angle order.
+fn dedupe_sweep(points: Vec<AngledScanLine>) -> Vec<AngledScanLine> {
+    use std::collections::HashMap;
+
+    let (overlap, mut rest): (Vec<_>, Vec<_>) = points.into_iter().partition(|point| {
+        let angle = point.angle.rem_euclid(360.0);
+        angle <= WRAP_OVERLAP_DEG || angle >= 360.0 - WRAP_OVERLAP_DEG
+    });
+
+    let mut bins: HashMap<i32, AngledScanLine> = HashMap::new();
+    for point in overlap {
+        let bin = (point.angle.rem_euclid(360.0) / WRAP_OVERLAP_BIN_DEG).round() as i32;
+        match bins.get(&bin) {
+            Some(existing) if existing.intensity >= point.intensity => {}
+            _ => { bins.insert(bin, point); }
+        }
+    }
+
+    let mut deduped_overlap: Vec<_> = bins.into_values().collect();
+    deduped_overlap.sort_by(|a, b| a.angle.total_cmp(&b.angle));
+
+    rest.extend(deduped_overlap);
+    rest
 }
 
 
@@ -142,6 +360,7 @@ mod tests {
     use crate::lidar::D300;
     use futures::StreamExt;
     use tokio_test::block_on;
+    use super::{crc8_update, D300Error};
 
     fn load_bin_file(filename: &str) -> Vec<u8> {
         fs::read(filename).unwrap()
@@ -160,4 +379,47 @@ mod tests {
             assert_eq!(first.distance, 2803);
         });
     }
+
+    /// Builds a single-point 0x54 frame (header + msg_info + body), appending
+    /// a correct trailing CRC8 computed the same way `read_frame` checks it.
+    fn build_frame(distance: u16, intensity: u8) -> Vec<u8> {
+        let header = 0x54u8;
+        let msg_info = 1u8; // message_type 0, len 1
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u16.to_le_bytes()); // speed
+        body.extend_from_slice(&0u16.to_le_bytes()); // start_angle
+        body.extend_from_slice(&distance.to_le_bytes());
+        body.push(intensity);
+        body.extend_from_slice(&10000u16.to_le_bytes()); // end_angle = 100.00deg
+        body.extend_from_slice(&0u16.to_le_bytes()); // ts
+
+        let crc = crc8_update(crc8_update(0, &[header, msg_info]), &body);
+
+        let mut frame = vec![header, msg_info];
+        frame.extend_from_slice(&body);
+        frame.push(crc);
+        frame
+    }
+
+    #[test]
+    fn test_crc_validates_and_resyncs() {
+        let good = build_frame(2803, 47);
+
+        let mut corrupt = build_frame(2803, 47);
+        let last = corrupt.len() - 2; // flip a body byte, leaving the trailing CRC stale
+        corrupt[last] ^= 0xFF;
+
+        let mut bytes = corrupt;
+        bytes.extend_from_slice(&good);
+        let mut d300 = D300::new(bytes.as_slice());
+
+        block_on(async move {
+            let err = d300.read_frame().await.unwrap_err();
+            assert!(matches!(err, D300Error::Crc { .. }));
+
+            let frame = d300.read_frame().await.unwrap();
+            assert_eq!(frame.data[0].distance, 2803);
+            assert_eq!(frame.data[0].intensity, 47);
+        });
+    }
 }